@@ -0,0 +1,24 @@
+//! The connection state machine.
+//!
+//! Replaces the old `is_connected: bool` / `active_connection: Option<String>`
+//! pair, which couldn't tell a user-initiated disconnect apart from the
+//! supervisor giving up on a server — the same "denied vs canceled"
+//! distinction request-driven Tauri apps need for their own prompts.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ConnectionState {
+    Disconnected,
+    Connecting { config_id: String },
+    Connected { config_id: String },
+    Reconnecting { config_id: String },
+    Failed { config_id: String, reason: String },
+}
+
+impl Default for ConnectionState {
+    fn default() -> Self {
+        ConnectionState::Disconnected
+    }
+}
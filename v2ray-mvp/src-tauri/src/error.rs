@@ -0,0 +1,59 @@
+//! The error type returned from every `#[tauri::command]`.
+//!
+//! Serializes as `{ "kind": "...", "message": ... }` instead of a bare
+//! string, so the frontend can match on `kind` rather than pattern-matching
+//! on English text. `Proxy`/`Subscription` nest the originating domain
+//! error instead of flattening it, so e.g. `NoActiveInterface` stays
+//! distinguishable from a generic command failure one level down.
+
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Debug, Error, Serialize)]
+#[serde(tag = "kind", content = "message")]
+pub enum AppError {
+    #[error("config not found")]
+    ConfigNotFound,
+    #[error("subscription not found")]
+    SubscriptionNotFound,
+    #[error("not connected")]
+    NotConnected,
+    #[error("could not determine the OS config directory")]
+    NoConfigDir,
+    #[error("failed to parse config: {0}")]
+    Parse(String),
+    #[error("failed to spawn v2ray: {0}")]
+    ProcessSpawn(String),
+    #[error("network error: {0}")]
+    Network(String),
+    #[error("proxy error: {0}")]
+    Proxy(crate::proxy::ProxyError),
+    #[error("subscription error: {0}")]
+    Subscription(crate::subscription::SubscriptionError),
+    #[error("io error: {0}")]
+    Io(String),
+}
+
+impl From<crate::proxy::ProxyError> for AppError {
+    fn from(e: crate::proxy::ProxyError) -> Self {
+        AppError::Proxy(e)
+    }
+}
+
+impl From<crate::subscription::SubscriptionError> for AppError {
+    fn from(e: crate::subscription::SubscriptionError) -> Self {
+        AppError::Subscription(e)
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Io(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        AppError::Io(e.to_string())
+    }
+}
@@ -3,13 +3,24 @@
     windows_subsystem = "windows"
 )]
 
+mod connection;
+mod error;
+mod probe;
+mod proxy;
+mod subscription;
+mod supervisor;
+
+use connection::ConnectionState;
+use error::AppError;
+use probe::ProbeResult;
+use proxy::{ProxySettings, SavedProxyState};
+use subscription::Subscription;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
-use std::process::{Command, Stdio};
 use std::sync::Mutex;
-use tauri::State;
+use tauri::{Manager, State};
 use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -18,51 +29,126 @@ struct V2RayConfig {
     name: String,
     server: String,
     config_json: String,
+    /// User-marked failover candidate: eligible to be rotated into by the
+    /// supervisor when the active server keeps failing.
+    #[serde(default)]
+    is_fallback: bool,
+    /// Set when this config came from a subscription, so a refresh can tell
+    /// it apart from configs the user pasted in by hand.
+    #[serde(default)]
+    subscription_id: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 struct AppState {
     configs: Vec<V2RayConfig>,
-    active_connection: Option<String>,
-    v2ray_process: Option<u32>,
+    #[serde(default)]
+    connection_state: ConnectionState,
+    #[serde(default)]
+    subscriptions: Vec<Subscription>,
+    #[serde(default)]
+    proxy_settings: ProxySettings,
+    /// Whatever the OS proxy looked like before we enabled ours; replayed on disconnect.
+    #[serde(default)]
+    proxy_backup: Option<SavedProxyState>,
 }
 
 type AppStateType = Mutex<AppState>;
+/// The running supervisor for the active connection, if any. Kept separate
+/// from `AppStateType` since it isn't (de)serializable process/task state.
+type SupervisorState = Mutex<Option<supervisor::Supervisor>>;
 
-fn get_config_dir() -> PathBuf {
-    let mut config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+fn get_config_dir() -> Result<PathBuf, AppError> {
+    let mut config_dir = dirs::config_dir().ok_or(AppError::NoConfigDir)?;
     config_dir.push("v2ray-mvp");
     if !config_dir.exists() {
-        fs::create_dir_all(&config_dir).unwrap();
+        fs::create_dir_all(&config_dir)?;
     }
-    config_dir
+    Ok(config_dir)
 }
 
-fn load_state() -> AppState {
-    let config_file = get_config_dir().join("state.json");
-    if config_file.exists() {
-        let content = fs::read_to_string(config_file).unwrap_or_default();
-        serde_json::from_str(&content).unwrap_or_else(|_| AppState {
-            configs: Vec::new(),
-            active_connection: None,
-            v2ray_process: None,
-        })
-    } else {
-        AppState {
-            configs: Vec::new(),
-            active_connection: None,
-            v2ray_process: None,
+fn load_state() -> Result<AppState, AppError> {
+    let config_file = get_config_dir()?.join("state.json");
+    if !config_file.exists() {
+        return Ok(AppState::default());
+    }
+    let content = fs::read_to_string(config_file)?;
+    // A malformed state file shouldn't crash the app on startup - fall back
+    // to a fresh state instead.
+    Ok(serde_json::from_str(&content).unwrap_or_default())
+}
+
+fn save_state(state: &AppState) -> Result<(), AppError> {
+    let config_file = get_config_dir()?.join("state.json");
+    let content = serde_json::to_string_pretty(state)?;
+    fs::write(config_file, content)?;
+    Ok(())
+}
+
+/// Splits a share-link query string (`a=1&b=2`) into a lookup table.
+fn parse_query_params(query: &str) -> HashMap<&str, &str> {
+    let mut params = HashMap::new();
+    for pair in query.split('&') {
+        if let Some((key, value)) = pair.split_once('=') {
+            params.insert(key, value);
         }
     }
+    params
 }
 
-fn save_state(state: &AppState) {
-    let config_file = get_config_dir().join("state.json");
-    let content = serde_json::to_string_pretty(state).unwrap();
-    fs::write(config_file, content).unwrap();
+/// Builds the `streamSettings` block shared by VLESS/VMess/Trojan outbounds:
+/// transport-specific settings (`ws`/`grpc`/`h2`) plus TLS/REALITY settings,
+/// so a share link actually round-trips into a working v2ray config instead
+/// of silently becoming plain TCP.
+fn build_stream_settings(network: &str, security: &str, params: &HashMap<&str, &str>) -> serde_json::Value {
+    let mut settings = serde_json::json!({
+        "network": network,
+        "security": security,
+    });
+
+    match network {
+        "ws" => {
+            settings["wsSettings"] = serde_json::json!({
+                "path": params.get("path").copied().unwrap_or("/"),
+                "headers": { "Host": params.get("host").copied().unwrap_or("") }
+            });
+        }
+        "grpc" => {
+            settings["grpcSettings"] = serde_json::json!({
+                "serviceName": params.get("serviceName").copied().unwrap_or("")
+            });
+        }
+        "h2" => {
+            settings["httpSettings"] = serde_json::json!({
+                "path": params.get("path").copied().unwrap_or("/"),
+                "host": [params.get("host").copied().unwrap_or("")]
+            });
+        }
+        _ => {}
+    }
+
+    match security {
+        "reality" => {
+            settings["realitySettings"] = serde_json::json!({
+                "serverName": params.get("sni").copied().unwrap_or("tesla.com"),
+                "publicKey": params.get("pbk").copied().unwrap_or(""),
+                "shortId": params.get("sid").copied().unwrap_or(""),
+                "fingerprint": params.get("fp").copied().unwrap_or("chrome"),
+            });
+        }
+        "tls" => {
+            settings["tlsSettings"] = serde_json::json!({
+                "serverName": params.get("sni").copied().unwrap_or(""),
+                "fingerprint": params.get("fp").copied().unwrap_or("chrome"),
+            });
+        }
+        _ => {}
+    }
+
+    settings
 }
 
-fn convert_to_v2ray_config(config_str: &str) -> Result<String, String> {
+fn convert_to_v2ray_config(config_str: &str, socks_port: u16) -> Result<String, String> {
     let config_str = config_str.trim();
 
     // If it's already JSON, return as-is
@@ -92,7 +178,7 @@ fn convert_to_v2ray_config(config_str: &str) -> Result<String, String> {
 
                     let v2ray_config = serde_json::json!({
                         "inbounds": [{
-                            "port": 1080,
+                            "port": socks_port,
                             "protocol": "socks",
                             "settings": { "auth": "noauth" }
                         }],
@@ -132,28 +218,14 @@ fn convert_to_v2ray_config(config_str: &str) -> Result<String, String> {
                 let server = server_port[0];
                 let port: u16 = server_port[1].parse().unwrap_or(443);
 
-                // Parse query parameters
-                let mut flow = "xtls-rprx-vision";
-                let mut security = "reality";
-                let mut sni = "tesla.com";
-
-                if query_split.len() > 1 {
-                    for param in query_split[1].split('&') {
-                        let kv: Vec<&str> = param.split('=').collect();
-                        if kv.len() == 2 {
-                            match kv[0] {
-                                "flow" => flow = kv[1],
-                                "security" => security = kv[1],
-                                "sni" => sni = kv[1],
-                                _ => {}
-                            }
-                        }
-                    }
-                }
+                let params = parse_query_params(query_split.get(1).copied().unwrap_or(""));
+                let flow = params.get("flow").copied().unwrap_or("xtls-rprx-vision");
+                let network = params.get("type").copied().unwrap_or("tcp");
+                let security = params.get("security").copied().unwrap_or("reality");
 
                 let v2ray_config = serde_json::json!({
                     "inbounds": [{
-                        "port": 1080,
+                        "port": socks_port,
                         "protocol": "socks",
                         "settings": { "auth": "noauth" }
                     }],
@@ -170,13 +242,7 @@ fn convert_to_v2ray_config(config_str: &str) -> Result<String, String> {
                                 }]
                             }]
                         },
-                        "streamSettings": {
-                            "network": "tcp",
-                            "security": security,
-                            "tlsSettings": {
-                                "serverName": sni
-                            }
-                        }
+                        "streamSettings": build_stream_settings(network, security, &params)
                     }]
                 });
                 return Ok(v2ray_config.to_string());
@@ -193,12 +259,27 @@ fn convert_to_v2ray_config(config_str: &str) -> Result<String, String> {
                     let address = vmess_config.get("add").and_then(|v| v.as_str()).unwrap_or("");
                     let port = vmess_config.get("port").and_then(|v| v.as_u64()).unwrap_or(443) as u16;
                     let uuid = vmess_config.get("id").and_then(|v| v.as_str()).unwrap_or("");
-                    let net = vmess_config.get("net").and_then(|v| v.as_str()).unwrap_or("tcp");
-                    let tls = vmess_config.get("tls").and_then(|v| v.as_str()).unwrap_or("");
+                    let network = vmess_config.get("net").and_then(|v| v.as_str()).unwrap_or("tcp");
+                    let security = match vmess_config.get("tls").and_then(|v| v.as_str()) {
+                        Some("tls") => "tls",
+                        _ => "none",
+                    };
+
+                    let mut params = HashMap::new();
+                    if let Some(path) = vmess_config.get("path").and_then(|v| v.as_str()) {
+                        params.insert("path", path);
+                        params.insert("serviceName", path);
+                    }
+                    if let Some(host) = vmess_config.get("host").and_then(|v| v.as_str()) {
+                        params.insert("host", host);
+                    }
+                    if let Some(sni) = vmess_config.get("sni").and_then(|v| v.as_str()) {
+                        params.insert("sni", sni);
+                    }
 
                     let v2ray_config = serde_json::json!({
                         "inbounds": [{
-                            "port": 1080,
+                            "port": socks_port,
                             "protocol": "socks",
                             "settings": { "auth": "noauth" }
                         }],
@@ -214,10 +295,7 @@ fn convert_to_v2ray_config(config_str: &str) -> Result<String, String> {
                                     }]
                                 }]
                             },
-                            "streamSettings": {
-                                "network": net,
-                                "security": if tls == "tls" { "tls" } else { "none" }
-                            }
+                            "streamSettings": build_stream_settings(network, security, &params)
                         }]
                     });
                     return Ok(v2ray_config.to_string());
@@ -226,6 +304,51 @@ fn convert_to_v2ray_config(config_str: &str) -> Result<String, String> {
         }
     }
 
+    // Convert Trojan to V2Ray config
+    if config_str.starts_with("trojan://") {
+        let url_part = config_str.trim_start_matches("trojan://");
+        let parts: Vec<&str> = url_part.split('#').collect();
+        let main_part = parts[0];
+
+        let query_split: Vec<&str> = main_part.split('?').collect();
+        let main_url = query_split[0];
+
+        let at_split: Vec<&str> = main_url.split('@').collect();
+        if at_split.len() == 2 {
+            let password = at_split[0];
+            let server_port: Vec<&str> = at_split[1].split(':').collect();
+
+            if server_port.len() == 2 {
+                let server = server_port[0];
+                let port: u16 = server_port[1].parse().unwrap_or(443);
+
+                let params = parse_query_params(query_split.get(1).copied().unwrap_or(""));
+                let network = params.get("type").copied().unwrap_or("tcp");
+                let security = params.get("security").copied().unwrap_or("tls");
+
+                let v2ray_config = serde_json::json!({
+                    "inbounds": [{
+                        "port": socks_port,
+                        "protocol": "socks",
+                        "settings": { "auth": "noauth" }
+                    }],
+                    "outbounds": [{
+                        "protocol": "trojan",
+                        "settings": {
+                            "servers": [{
+                                "address": server,
+                                "port": port,
+                                "password": password
+                            }]
+                        },
+                        "streamSettings": build_stream_settings(network, security, &params)
+                    }]
+                });
+                return Ok(v2ray_config.to_string());
+            }
+        }
+    }
+
     Err("Unsupported config format".to_string())
 }
 
@@ -332,254 +455,341 @@ fn parse_v2ray_config(config_str: &str) -> Result<(String, String), String> {
 }
 
 #[tauri::command]
-async fn get_configs(state: State<'_, AppStateType>) -> Result<Vec<V2RayConfig>, String> {
+async fn get_configs(state: State<'_, AppStateType>) -> Result<Vec<V2RayConfig>, AppError> {
     let app_state = state.lock().unwrap();
     Ok(app_state.configs.clone())
 }
 
 #[tauri::command]
-async fn add_config(config: String, state: State<'_, AppStateType>) -> Result<(), String> {
-    let (name, server) = parse_v2ray_config(&config)?;
-    let v2ray_json = convert_to_v2ray_config(&config)?;
+async fn add_config(config: String, state: State<'_, AppStateType>) -> Result<(), AppError> {
+    let (name, server) = parse_v2ray_config(&config).map_err(AppError::Parse)?;
+
+    let mut app_state = state.lock().unwrap();
+    let v2ray_json = convert_to_v2ray_config(&config, app_state.proxy_settings.socks_port).map_err(AppError::Parse)?;
 
     let new_config = V2RayConfig {
         id: Uuid::new_v4().to_string(),
         name,
         server,
         config_json: v2ray_json,
+        is_fallback: false,
+        subscription_id: None,
     };
 
-    let mut app_state = state.lock().unwrap();
     app_state.configs.push(new_config);
-    save_state(&app_state);
+    save_state(&app_state)?;
 
     Ok(())
 }
 
 #[tauri::command]
-async fn remove_config(id: String, state: State<'_, AppStateType>) -> Result<(), String> {
+async fn remove_config(id: String, state: State<'_, AppStateType>) -> Result<(), AppError> {
     let mut app_state = state.lock().unwrap();
     app_state.configs.retain(|c| c.id != id);
-    save_state(&app_state);
+    save_state(&app_state)?;
 
     Ok(())
 }
 
 #[tauri::command]
-async fn connect(id: String, state: State<'_, AppStateType>) -> Result<(), String> {
-    let mut app_state = state.lock().unwrap();
+async fn get_subscriptions(state: State<'_, AppStateType>) -> Result<Vec<Subscription>, AppError> {
+    let app_state = state.lock().unwrap();
+    Ok(app_state.subscriptions.clone())
+}
 
-    // Find config
-    let config = app_state.configs.iter().find(|c| c.id == id).ok_or("Config not found")?;
-
-    // Stop existing connection
-    if let Some(_) = app_state.active_connection {
-        // Kill existing v2ray process
-        #[cfg(target_os = "windows")]
-        {
-            Command::new("taskkill")
-                .args(["/F", "/IM", "v2ray.exe"])
-                .output()
-                .ok();
-        }
-        #[cfg(not(target_os = "windows"))]
-        {
-            Command::new("pkill")
-                .arg("v2ray")
-                .output()
-                .ok();
-        }
+/// Fetches a subscription URL, decodes the bulk server list, and imports
+/// each entry through the normal single-config pipeline.
+#[tauri::command]
+async fn add_subscription(name: String, url: String, state: State<'_, AppStateType>) -> Result<(), AppError> {
+    let links = subscription::fetch_links(&url).await?;
+
+    let mut app_state = state.lock().unwrap();
+    let socks_port = app_state.proxy_settings.socks_port;
+    let subscription_id = Uuid::new_v4().to_string();
+
+    for link in &links {
+        let Ok((config_name, server)) = parse_v2ray_config(link) else { continue };
+        let Ok(config_json) = convert_to_v2ray_config(link, socks_port) else { continue };
+
+        app_state.configs.push(V2RayConfig {
+            id: Uuid::new_v4().to_string(),
+            name: config_name,
+            server,
+            config_json,
+            is_fallback: false,
+            subscription_id: Some(subscription_id.clone()),
+        });
     }
 
-    // Write config to temporary file
-    let config_dir = get_config_dir();
-    let config_file = config_dir.join("current_config.json");
-    fs::write(&config_file, &config.config_json).map_err(|e| e.to_string())?;
+    app_state.subscriptions.push(Subscription {
+        id: subscription_id,
+        name,
+        url,
+        last_updated: subscription::now_unix(),
+    });
+    save_state(&app_state)?;
+
+    Ok(())
+}
 
-    // Start v2ray process
-    let v2ray_cmd = if cfg!(target_os = "windows") {
-        "v2ray.exe"
-    } else {
-        "v2ray"
+/// Re-fetches a subscription and diffs it against the configs already
+/// imported from it: new servers are added, stale ones removed, and any
+/// manually-added config is left untouched.
+#[tauri::command]
+async fn refresh_subscription(id: String, state: State<'_, AppStateType>) -> Result<(), AppError> {
+    let url = {
+        let app_state = state.lock().unwrap();
+        app_state
+            .subscriptions
+            .iter()
+            .find(|s| s.id == id)
+            .ok_or(AppError::SubscriptionNotFound)?
+            .url
+            .clone()
     };
 
-    let child = Command::new(v2ray_cmd)
-        .arg("-config")
-        .arg(&config_file)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn()
-        .map_err(|e| format!("Failed to start v2ray: {}", e))?;
+    let links = subscription::fetch_links(&url).await?;
+
+    let mut app_state = state.lock().unwrap();
+    let socks_port = app_state.proxy_settings.socks_port;
+
+    let mut fresh = Vec::new();
+    for link in &links {
+        let Ok((name, server)) = parse_v2ray_config(link) else { continue };
+        let Ok(config_json) = convert_to_v2ray_config(link, socks_port) else { continue };
+        fresh.push((name, server, config_json));
+    }
 
-    app_state.active_connection = Some(id);
-    app_state.v2ray_process = Some(child.id());
-    save_state(&app_state);
+    app_state.configs.retain(|c| {
+        c.subscription_id.as_deref() != Some(id.as_str())
+            || fresh.iter().any(|(name, server, _)| &c.name == name && &c.server == server)
+    });
+
+    for (name, server, config_json) in fresh {
+        let already_present = app_state.configs.iter().any(|c| {
+            c.subscription_id.as_deref() == Some(id.as_str()) && c.name == name && c.server == server
+        });
+        if !already_present {
+            app_state.configs.push(V2RayConfig {
+                id: Uuid::new_v4().to_string(),
+                name,
+                server,
+                config_json,
+                is_fallback: false,
+                subscription_id: Some(id.clone()),
+            });
+        }
+    }
 
-    // Set system proxy
-    set_system_proxy(true)?;
+    if let Some(subscription) = app_state.subscriptions.iter_mut().find(|s| s.id == id) {
+        subscription.last_updated = subscription::now_unix();
+    }
+    save_state(&app_state)?;
 
     Ok(())
 }
 
+/// Marks (or unmarks) a config as a failover candidate the supervisor may
+/// rotate into when the active server keeps failing.
 #[tauri::command]
-async fn disconnect(state: State<'_, AppStateType>) -> Result<(), String> {
+async fn set_fallback(id: String, enabled: bool, state: State<'_, AppStateType>) -> Result<(), AppError> {
     let mut app_state = state.lock().unwrap();
+    let config = app_state.configs.iter_mut().find(|c| c.id == id).ok_or(AppError::ConfigNotFound)?;
+    config.is_fallback = enabled;
+    save_state(&app_state)?;
 
-    // Kill v2ray process
-    #[cfg(target_os = "windows")]
-    {
-        Command::new("taskkill")
-            .args(["/F", "/IM", "v2ray.exe"])
-            .output()
-            .ok();
-    }
-    #[cfg(not(target_os = "windows"))]
-    {
-        Command::new("pkill")
-            .arg("v2ray")
-            .output()
-            .ok();
+    Ok(())
+}
+
+#[tauri::command]
+async fn connect(
+    id: String,
+    state: State<'_, AppStateType>,
+    supervisor_state: State<'_, SupervisorState>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), AppError> {
+    // Tear down any previous supervisor before starting a new one.
+    let previous = supervisor_state.lock().unwrap().take();
+    if let Some(previous) = previous {
+        previous.cancel().await;
     }
 
-    app_state.active_connection = None;
-    app_state.v2ray_process = None;
-    save_state(&app_state);
+    let candidates = {
+        let app_state = state.lock().unwrap();
+        let socks_port = app_state.proxy_settings.socks_port;
+        let config = app_state.configs.iter().find(|c| c.id == id).ok_or(AppError::ConfigNotFound)?;
+        let mut candidates =
+            vec![supervisor::Candidate { id: config.id.clone(), config_json: config.config_json.clone(), socks_port }];
+        candidates.extend(
+            app_state
+                .configs
+                .iter()
+                .filter(|c| c.is_fallback && c.id != id)
+                .map(|c| supervisor::Candidate { id: c.id.clone(), config_json: c.config_json.clone(), socks_port }),
+        );
+        candidates
+    };
 
-    // Unset system proxy
-    set_system_proxy(false)?;
+    let config_dir = get_config_dir()?;
+
+    // Enable the system proxy before starting the supervisor: if this fails
+    // we bail out before any background v2ray process exists, instead of
+    // leaking a live supervisor/connection the caller thinks never started.
+    let backup = {
+        let app_state = state.lock().unwrap();
+        proxy::enable(&app_state.proxy_settings)?
+    };
+
+    let events_handle = app_handle.clone();
+    let supervisor = supervisor::Supervisor::spawn(candidates, config_dir, move |new_state| {
+        if let Some(state) = events_handle.try_state::<AppStateType>() {
+            let mut app_state = state.lock().unwrap();
+            app_state.connection_state = new_state.clone();
+            let _ = save_state(&app_state);
+        }
+        let _ = events_handle.emit_all(supervisor::EVENT_NAME, new_state);
+    });
+    *supervisor_state.lock().unwrap() = Some(supervisor);
+
+    // Don't set `connection_state` here: the supervisor's background task is
+    // already running and its own `on_state` callback may have already
+    // advanced past `Connecting` by the time we'd get the lock, so writing it
+    // directly here risks clobbering a newer state with a stale one.
+    let mut app_state = state.lock().unwrap();
+    app_state.proxy_backup = Some(backup);
+    save_state(&app_state)?;
 
     Ok(())
 }
 
 #[tauri::command]
-async fn is_connected(state: State<'_, AppStateType>) -> Result<bool, String> {
+async fn disconnect(state: State<'_, AppStateType>, supervisor_state: State<'_, SupervisorState>) -> Result<(), AppError> {
+    let supervisor = supervisor_state.lock().unwrap().take();
+    let Some(supervisor) = supervisor else {
+        return Err(AppError::NotConnected);
+    };
+    supervisor.cancel().await;
+
+    let mut app_state = state.lock().unwrap();
+    // A user-initiated disconnect always wins over whatever the supervisor
+    // last reported, so it isn't mistaken for a failure.
+    app_state.connection_state = ConnectionState::Disconnected;
+
+    // Restore whatever proxy configuration we found on connect.
+    if let Some(backup) = app_state.proxy_backup.take() {
+        proxy::disable(&backup)?;
+    }
+    save_state(&app_state)?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_connection_state(state: State<'_, AppStateType>) -> Result<ConnectionState, AppError> {
     let app_state = state.lock().unwrap();
-    Ok(app_state.active_connection.is_some())
+    Ok(app_state.connection_state.clone())
 }
 
 #[tauri::command]
-async fn ping_test(id: String, state: State<'_, AppStateType>) -> Result<u64, String> {
-    // Scope for the MutexGuard
-    {
+async fn get_proxy_settings(state: State<'_, AppStateType>) -> Result<ProxySettings, AppError> {
+    let app_state = state.lock().unwrap();
+    Ok(app_state.proxy_settings.clone())
+}
+
+/// Lets the UI change the SOCKS port / bypass list instead of them being
+/// stuck at `ProxySettings::default()` forever.
+#[tauri::command]
+async fn update_proxy_settings(settings: ProxySettings, state: State<'_, AppStateType>) -> Result<(), AppError> {
+    let mut app_state = state.lock().unwrap();
+    app_state.proxy_settings = settings;
+    save_state(&app_state)?;
+    Ok(())
+}
+
+/// Measures latency to a fixed endpoint through a config's real tunnel: spins
+/// up a one-off v2ray instance on a scratch port rather than reusing the
+/// active connection, so a config can be tested without being connected to.
+#[tauri::command]
+async fn ping_test(id: String, state: State<'_, AppStateType>) -> Result<u64, AppError> {
+    let config_json = {
         let app_state = state.lock().unwrap();
-        // Check if config exists, but don't hold the lock longer than necessary.
-        if !app_state.configs.iter().any(|c| c.id == id) {
-            return Err("Config not found".to_string());
-        }
-    } // MutexGuard (`app_state`) is dropped here
-
-    // Simple ping test to Google DNS
-    let start = std::time::Instant::now();
-    // Create a new client for this request to ensure Send safety if client isn't inherently Send
-    let client = reqwest::Client::new();
-    let response = client.get("https://8.8.8.8").send().await;
-    let duration = start.elapsed();
-
-    match response {
-        Ok(res) if res.status().is_success() => Ok(duration.as_millis() as u64),
-        Ok(res) => Err(format!("Ping request returned non-OK status: {}", res.status())),
-        Err(e) => Err(format!("Ping failed: {}", e)),
-    }
+        app_state.configs.iter().find(|c| c.id == id).ok_or(AppError::ConfigNotFound)?.config_json.clone()
+    };
+    probe::ping(&config_json, &get_config_dir()?).await
 }
 
-fn set_system_proxy(enable: bool) -> Result<(), String> {
-    #[cfg(target_os = "windows")]
-    {
-        if enable {
-            Command::new("reg")
-                .args([
-                    "add",
-                    "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings",
-                    "/v", "ProxyEnable",
-                    "/t", "REG_DWORD",
-                    "/d", "1",
-                    "/f"
-                ])
-                .output()
-                .map_err(|e| e.to_string())?;
-
-            Command::new("reg")
-                .args([
-                    "add",
-                    "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings",
-                    "/v", "ProxyServer",
-                    "/t", "REG_SZ",
-                    "/d", "127.0.0.1:1080",
-                    "/f"
-                ])
-                .output()
-                .map_err(|e| e.to_string())?;
-        } else {
-            Command::new("reg")
-                .args([
-                    "add",
-                    "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings",
-                    "/v", "ProxyEnable",
-                    "/t", "REG_DWORD",
-                    "/d", "0",
-                    "/f"
-                ])
-                .output()
-                .map_err(|e| e.to_string())?;
-        }
-    }
+/// Downloads a fixed-size payload through a config's own tunnel and reports
+/// throughput in kilobits/sec, using its own one-off scratch instance, as
+/// `ping_test` does.
+#[tauri::command]
+async fn speed_test(id: String, state: State<'_, AppStateType>) -> Result<u64, AppError> {
+    let config_json = {
+        let app_state = state.lock().unwrap();
+        app_state.configs.iter().find(|c| c.id == id).ok_or(AppError::ConfigNotFound)?.config_json.clone()
+    };
+    probe::speed(&config_json, &get_config_dir()?).await
+}
 
-    #[cfg(target_os = "macos")]
-    {
-        if enable {
-            Command::new("networksetup")
-                .args(["-setsocksfirewallproxy", "Wi-Fi", "127.0.0.1", "1080"])
-                .output()
-                .map_err(|e| e.to_string())?;
-        } else {
-            Command::new("networksetup")
-                .args(["-setsocksfirewallproxystate", "Wi-Fi", "off"])
-                .output()
-                .map_err(|e| e.to_string())?;
-        }
-    }
+/// Pings and speed-tests every stored config concurrently so the UI can
+/// sort servers by real (tunnelled) latency and bandwidth.
+#[tauri::command]
+async fn test_all(state: State<'_, AppStateType>) -> Result<Vec<ProbeResult>, AppError> {
+    let configs: Vec<(String, String)> = {
+        let app_state = state.lock().unwrap();
+        app_state.configs.iter().map(|c| (c.id.clone(), c.config_json.clone())).collect()
+    };
+    let config_dir = get_config_dir()?;
+
+    let handles: Vec<_> = configs
+        .into_iter()
+        .map(|(id, config_json)| {
+            let config_dir = config_dir.clone();
+            tokio::spawn(async move {
+                match probe::ping(&config_json, &config_dir).await {
+                    Ok(latency_ms) => {
+                        let throughput_kbps = probe::speed(&config_json, &config_dir).await.ok();
+                        ProbeResult { id, latency_ms: Some(latency_ms), throughput_kbps, error: None }
+                    }
+                    Err(e) => ProbeResult { id, latency_ms: None, throughput_kbps: None, error: Some(e.to_string()) },
+                }
+            })
+        })
+        .collect();
 
-    #[cfg(target_os = "linux")]
-    {
-        // Linux proxy settings vary by desktop environment
-        // This is a simplified approach using gsettings for GNOME
-        if enable {
-            Command::new("gsettings")
-                .args(["set", "org.gnome.system.proxy.socks", "host", "127.0.0.1"])
-                .output()
-                .ok();
-            Command::new("gsettings")
-                .args(["set", "org.gnome.system.proxy.socks", "port", "1080"])
-                .output()
-                .ok();
-            Command::new("gsettings")
-                .args(["set", "org.gnome.system.proxy", "mode", "manual"])
-                .output()
-                .ok();
-        } else {
-            Command::new("gsettings")
-                .args(["set", "org.gnome.system.proxy", "mode", "none"])
-                .output()
-                .ok();
+    let mut results = Vec::with_capacity(handles.len());
+    for handle in handles {
+        if let Ok(result) = handle.await {
+            results.push(result);
         }
     }
-
-    Ok(())
+    Ok(results)
 }
 
 fn main() {
-    let initial_state = load_state();
+    let initial_state = load_state().unwrap_or_else(|e| {
+        eprintln!("failed to load saved state, starting fresh: {e}");
+        AppState::default()
+    });
 
     tauri::Builder::default()
         .manage(AppStateType::new(initial_state))
+        .manage(SupervisorState::default())
         .invoke_handler(tauri::generate_handler![
             get_configs,
             add_config,
             remove_config,
+            get_subscriptions,
+            add_subscription,
+            refresh_subscription,
+            set_fallback,
             connect,
             disconnect,
-            is_connected,
-            ping_test
+            get_connection_state,
+            get_proxy_settings,
+            update_proxy_settings,
+            ping_test,
+            speed_test,
+            test_all
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,128 @@
+//! One-off v2ray probes: latency and throughput measurements through a
+//! config's real tunnel, without promoting it to the active connection.
+//!
+//! Each probe spins up its own v2ray instance on a scratch port so it can
+//! run alongside (or instead of) an already-connected tunnel.
+
+use crate::error::AppError;
+use reqwest::Proxy;
+use serde::Serialize;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+const STARTUP_GRACE: Duration = Duration::from_millis(500);
+const PING_URL: &str = "https://8.8.8.8";
+const SPEED_TEST_URL: &str = "https://speed.cloudflare.com/__down?bytes=10000000";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeResult {
+    pub id: String,
+    pub latency_ms: Option<u64>,
+    pub throughput_kbps: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Picks an OS-assigned free port by binding then immediately releasing a
+/// listener; v2ray gets the window between the drop and its own bind.
+fn scratch_port() -> Result<u16, AppError> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.port())
+}
+
+/// Rewrites a stored config's SOCKS inbound to `port` so the probe never
+/// collides with the active connection (if any).
+fn with_scratch_port(config_json: &str, port: u16) -> Result<String, AppError> {
+    let mut value: serde_json::Value = serde_json::from_str(config_json)?;
+    if let Some(inbound) = value.get_mut("inbounds").and_then(|v| v.get_mut(0)) {
+        inbound["port"] = serde_json::json!(port);
+    }
+    Ok(value.to_string())
+}
+
+/// Owns the scratch v2ray child + its temp config file; both are cleaned up
+/// when the probe is done, success or not.
+struct ScratchV2Ray {
+    child: Child,
+    config_file: PathBuf,
+}
+
+impl Drop for ScratchV2Ray {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = std::fs::remove_file(&self.config_file);
+    }
+}
+
+fn spawn_scratch(config_json: &str, config_dir: &Path, port: u16) -> Result<ScratchV2Ray, AppError> {
+    let scratch_json = with_scratch_port(config_json, port)?;
+    let config_file = config_dir.join(format!("probe-{port}.json"));
+    std::fs::write(&config_file, &scratch_json)?;
+
+    let v2ray_cmd = if cfg!(target_os = "windows") { "v2ray.exe" } else { "v2ray" };
+    let child = Command::new(v2ray_cmd)
+        .arg("-config")
+        .arg(&config_file)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| AppError::ProcessSpawn(e.to_string()))?;
+
+    Ok(ScratchV2Ray { child, config_file })
+}
+
+/// Bounded so a dead/unreachable server - the exact case these probes exist
+/// to catch - fails fast instead of hanging the command forever.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+const PROBE_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn proxied_client(port: u16) -> Result<reqwest::Client, AppError> {
+    let proxy = Proxy::all(format!("socks5://127.0.0.1:{port}")).map_err(|e| AppError::Network(e.to_string()))?;
+    reqwest::Client::builder()
+        .proxy(proxy)
+        .timeout(PROBE_TIMEOUT)
+        .connect_timeout(PROBE_CONNECT_TIMEOUT)
+        .build()
+        .map_err(|e| AppError::Network(e.to_string()))
+}
+
+/// Measures round-trip latency to a fixed endpoint through `config_json`'s
+/// own tunnel.
+pub async fn ping(config_json: &str, config_dir: &Path) -> Result<u64, AppError> {
+    let port = scratch_port()?;
+    let _v2ray = spawn_scratch(config_json, config_dir, port)?;
+    tokio::time::sleep(STARTUP_GRACE).await;
+
+    let client = proxied_client(port)?;
+    let start = std::time::Instant::now();
+    let response = client.get(PING_URL).send().await.map_err(|e| AppError::Network(e.to_string()))?;
+    let duration = start.elapsed();
+
+    if !response.status().is_success() {
+        return Err(AppError::Network(format!("ping request returned non-OK status: {}", response.status())));
+    }
+    Ok(duration.as_millis() as u64)
+}
+
+/// Downloads a fixed-size payload through `config_json`'s tunnel and
+/// reports throughput in kilobits/sec.
+pub async fn speed(config_json: &str, config_dir: &Path) -> Result<u64, AppError> {
+    let port = scratch_port()?;
+    let _v2ray = spawn_scratch(config_json, config_dir, port)?;
+    tokio::time::sleep(STARTUP_GRACE).await;
+
+    let client = proxied_client(port)?;
+    let start = std::time::Instant::now();
+    let bytes = client
+        .get(SPEED_TEST_URL)
+        .send()
+        .await
+        .map_err(|e| AppError::Network(e.to_string()))?
+        .bytes()
+        .await
+        .map_err(|e| AppError::Network(e.to_string()))?;
+    let elapsed = start.elapsed().as_secs_f64().max(0.001);
+
+    Ok(((bytes.len() as f64 * 8.0) / 1000.0 / elapsed) as u64)
+}
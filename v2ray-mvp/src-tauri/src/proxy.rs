@@ -0,0 +1,282 @@
+//! Cross-platform system proxy management.
+//!
+//! Unlike the previous ad-hoc shell-outs, this module remembers the proxy
+//! configuration the user already had before we touched it and puts it back
+//! on disconnect, the way sysproxy-rs does it. On Windows that is the
+//! `ProxyEnable`/`ProxyServer`/`ProxyOverride` values; on macOS it is the
+//! active service's SOCKS proxy (its bypass domains are not restored). On
+//! Linux nothing is captured: `disable` sets the GNOME proxy mode to `none`.
+
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+/// Bypass/no-proxy targets and the local SOCKS port we point the OS at.
+/// Lives on `AppState` so it can be edited from the UI instead of being
+/// baked into the binary.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProxySettings {
+    pub socks_port: u16,
+    pub bypass: Vec<String>,
+}
+
+impl Default for ProxySettings {
+    fn default() -> Self {
+        Self {
+            socks_port: 1080,
+            bypass: vec![
+                "127.0.0.1".to_string(),
+                "localhost".to_string(),
+                "10.0.0.0/8".to_string(),
+                "172.16.0.0/12".to_string(),
+                "192.168.0.0/16".to_string(),
+            ],
+        }
+    }
+}
+
+/// Serializes as `{ "kind": "...", "message": "..." }` so `AppError::Proxy`
+/// can nest it without flattening it into an opaque string, letting the
+/// frontend tell e.g. `NoActiveInterface` apart from a generic command
+/// failure.
+#[derive(thiserror::Error, Debug, Serialize)]
+#[serde(tag = "kind", content = "message")]
+pub enum ProxyError {
+    #[error("failed to run `{0}`")]
+    CommandFailed(String),
+    #[error("`{0}` exited with a non-zero status")]
+    CommandNonZero(String),
+    #[error("could not determine the active network interface/service")]
+    NoActiveInterface,
+    #[error("no saved proxy state to restore")]
+    NothingToRestore,
+}
+
+/// Whatever the OS proxy settings looked like before we enabled ours.
+/// Captured on connect, replayed on disconnect. Has no fields on Linux.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SavedProxyState {
+    #[cfg(target_os = "windows")]
+    pub proxy_enable: u32,
+    #[cfg(target_os = "windows")]
+    pub proxy_server: String,
+    #[cfg(target_os = "windows")]
+    pub proxy_override: String,
+
+    #[cfg(target_os = "macos")]
+    pub service: String,
+    #[cfg(target_os = "macos")]
+    pub was_enabled: bool,
+    #[cfg(target_os = "macos")]
+    pub host: String,
+    #[cfg(target_os = "macos")]
+    pub port: String,
+}
+
+fn run(program: &'static str, args: &[&str]) -> Result<std::process::Output, ProxyError> {
+    Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| ProxyError::CommandFailed(format!("{program}: {e}")))
+}
+
+fn run_checked(program: &'static str, args: &[&str]) -> Result<std::process::Output, ProxyError> {
+    let output = run(program, args)?;
+    if !output.status.success() {
+        return Err(ProxyError::CommandNonZero(format!("{program} exited with status {}", output.status)));
+    }
+    Ok(output)
+}
+
+/// Enables the system SOCKS proxy, returning whatever was configured
+/// beforehand so `disable` can restore it.
+pub fn enable(settings: &ProxySettings) -> Result<SavedProxyState, ProxyError> {
+    #[cfg(target_os = "windows")]
+    {
+        windows::enable(settings)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        macos::enable(settings)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        linux::enable(settings)
+    }
+}
+
+/// Restores the proxy configuration captured by `enable`.
+pub fn disable(saved: &SavedProxyState) -> Result<(), ProxyError> {
+    #[cfg(target_os = "windows")]
+    {
+        windows::disable(saved)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        macos::disable(saved)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        linux::disable(saved)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use super::*;
+
+    const KEY: &str = "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings";
+
+    fn query(value: &str) -> Option<String> {
+        let output = run("reg", &["query", KEY, "/v", value]).ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let text = String::from_utf8_lossy(&output.stdout);
+        // Lines look like: "    ProxyServer    REG_SZ    127.0.0.1:1080"
+        text.lines()
+            .find(|line| line.trim_start().starts_with(value))
+            .and_then(|line| line.split_whitespace().last())
+            .map(|s| s.to_string())
+    }
+
+    pub fn enable(settings: &ProxySettings) -> Result<SavedProxyState, ProxyError> {
+        let saved = SavedProxyState {
+            // `reg query` prints REG_DWORD values in hex (e.g. "0x1"), not decimal.
+            proxy_enable: query("ProxyEnable")
+                .and_then(|v| u32::from_str_radix(v.trim_start_matches("0x"), 16).ok())
+                .unwrap_or(0),
+            proxy_server: query("ProxyServer").unwrap_or_default(),
+            proxy_override: query("ProxyOverride").unwrap_or_default(),
+        };
+
+        run_checked("reg", &["add", KEY, "/v", "ProxyEnable", "/t", "REG_DWORD", "/d", "1", "/f"])?;
+        run_checked(
+            "reg",
+            &[
+                "add", KEY, "/v", "ProxyServer", "/t", "REG_SZ", "/d",
+                &format!("socks=127.0.0.1:{}", settings.socks_port), "/f",
+            ],
+        )?;
+        run_checked(
+            "reg",
+            &["add", KEY, "/v", "ProxyOverride", "/t", "REG_SZ", "/d", &settings.bypass.join(";"), "/f"],
+        )?;
+
+        Ok(saved)
+    }
+
+    pub fn disable(saved: &SavedProxyState) -> Result<(), ProxyError> {
+        run_checked(
+            "reg",
+            &["add", KEY, "/v", "ProxyEnable", "/t", "REG_DWORD", "/d", &saved.proxy_enable.to_string(), "/f"],
+        )?;
+        run_checked("reg", &["add", KEY, "/v", "ProxyServer", "/t", "REG_SZ", "/d", &saved.proxy_server, "/f"])?;
+        run_checked(
+            "reg",
+            &["add", KEY, "/v", "ProxyOverride", "/t", "REG_SZ", "/d", &saved.proxy_override, "/f"],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::*;
+
+    /// The network service (e.g. "Wi-Fi", "USB 10/100/1000 LAN") that
+    /// currently carries the default route, instead of assuming "Wi-Fi".
+    fn active_service() -> Result<String, ProxyError> {
+        let route = run_checked("route", &["-n", "get", "default"])?;
+        let route_out = String::from_utf8_lossy(&route.stdout);
+        let interface = route_out
+            .lines()
+            .find_map(|l| l.trim().strip_prefix("interface: "))
+            .ok_or(ProxyError::NoActiveInterface)?
+            .to_string();
+
+        let order = run_checked("networksetup", &["-listnetworkserviceorder"])?;
+        let order_out = String::from_utf8_lossy(&order.stdout);
+        // Entries look like:
+        // (1) Wi-Fi
+        // (Hardware Port: Wi-Fi, Device: en0)
+        let mut lines = order_out.lines().peekable();
+        while let Some(line) = lines.next() {
+            if let Some(name) = line.strip_prefix("(Hardware Port: ") {
+                // no-op: device lines are matched through `peek()` below
+                let _ = name;
+            }
+            if line.starts_with('(') && line.contains(')') {
+                if let Some(device_line) = lines.peek() {
+                    if device_line.contains(&format!("Device: {}", interface)) {
+                        if let Some(name) = line.splitn(2, ')').nth(1) {
+                            return Ok(name.trim().to_string());
+                        }
+                    }
+                }
+            }
+        }
+        Err(ProxyError::NoActiveInterface)
+    }
+
+    pub fn enable(settings: &ProxySettings) -> Result<SavedProxyState, ProxyError> {
+        let service = active_service()?;
+
+        let current = run_checked("networksetup", &["-getsocksfirewallproxy", &service])?;
+        let current_out = String::from_utf8_lossy(&current.stdout);
+        let was_enabled = current_out.lines().any(|l| l.starts_with("Enabled: Yes"));
+        let host = current_out
+            .lines()
+            .find_map(|l| l.strip_prefix("Server: "))
+            .unwrap_or("")
+            .to_string();
+        let port = current_out
+            .lines()
+            .find_map(|l| l.strip_prefix("Port: "))
+            .unwrap_or("")
+            .to_string();
+
+        run_checked(
+            "networksetup",
+            &["-setsocksfirewallproxy", &service, "127.0.0.1", &settings.socks_port.to_string()],
+        )?;
+        let mut bypass_args = vec!["-setproxybypassdomains", &service];
+        bypass_args.extend(settings.bypass.iter().map(|s| s.as_str()));
+        run_checked("networksetup", &bypass_args)?;
+
+        Ok(SavedProxyState { service, was_enabled, host, port })
+    }
+
+    pub fn disable(saved: &SavedProxyState) -> Result<(), ProxyError> {
+        if saved.was_enabled && !saved.host.is_empty() {
+            run_checked(
+                "networksetup",
+                &["-setsocksfirewallproxy", &saved.service, &saved.host, &saved.port],
+            )?;
+        } else {
+            run_checked("networksetup", &["-setsocksfirewallproxystate", &saved.service, "off"])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+
+    pub fn enable(settings: &ProxySettings) -> Result<SavedProxyState, ProxyError> {
+        run_checked("gsettings", &["set", "org.gnome.system.proxy.socks", "host", "127.0.0.1"])?;
+        run_checked(
+            "gsettings",
+            &["set", "org.gnome.system.proxy.socks", "port", &settings.socks_port.to_string()],
+        )?;
+        let ignore_hosts = format!("[{}]", settings.bypass.iter().map(|s| format!("'{}'", s)).collect::<Vec<_>>().join(", "));
+        run_checked("gsettings", &["set", "org.gnome.system.proxy", "ignore-hosts", &ignore_hosts])?;
+        run_checked("gsettings", &["set", "org.gnome.system.proxy", "mode", "manual"])?;
+        Ok(SavedProxyState::default())
+    }
+
+    pub fn disable(_saved: &SavedProxyState) -> Result<(), ProxyError> {
+        run_checked("gsettings", &["set", "org.gnome.system.proxy", "mode", "none"])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+//! Remote subscription support: bulk-import a server list from a single
+//! base64-encoded URL, Clash-subscription style, and keep it in sync.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Subscription {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub last_updated: u64,
+}
+
+/// Serializes as `{ "kind": "...", "message": "..." }` so `AppError::Subscription`
+/// can nest it without flattening it into an opaque string, letting the
+/// frontend tell a fetch failure apart from a malformed subscription body.
+#[derive(thiserror::Error, Debug, Serialize)]
+#[serde(tag = "kind", content = "message")]
+pub enum SubscriptionError {
+    #[error("failed to fetch subscription: {0}")]
+    Fetch(String),
+    #[error("subscription body is not valid base64")]
+    Decode,
+    #[error("subscription body is not valid UTF-8")]
+    Utf8,
+}
+
+/// Fetches a subscription URL and decodes it into raw share links (one per
+/// non-empty line), ready to go through `parse_v2ray_config`/
+/// `convert_to_v2ray_config`.
+pub async fn fetch_links(url: &str) -> Result<Vec<String>, SubscriptionError> {
+    let body = reqwest::get(url)
+        .await
+        .map_err(|e| SubscriptionError::Fetch(e.to_string()))?
+        .text()
+        .await
+        .map_err(|e| SubscriptionError::Fetch(e.to_string()))?;
+    let decoded = base64::decode(body.trim()).map_err(|_| SubscriptionError::Decode)?;
+    let text = String::from_utf8(decoded).map_err(|_| SubscriptionError::Utf8)?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+pub fn now_unix() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,206 @@
+//! Connection supervisor: watches the spawned v2ray process and restarts it
+//! on unexpected exit or a failed through-tunnel health probe, with
+//! exponential backoff + jitter and failover across a user-ordered list of
+//! candidate configs, mirroring how NATS-style reconnect logic handles a
+//! flaky broker.
+
+use crate::connection::ConnectionState;
+use rand::Rng;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const FAILURES_BEFORE_ROTATE: u32 = 3;
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const HEALTH_PROBE_URL: &str = "https://8.8.8.8";
+const HEALTH_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tauri event name the caller's `on_state` callback is expected to re-emit
+/// as `connection-state-changed`, so the UI can show live status.
+pub const EVENT_NAME: &str = "connection-state-changed";
+
+/// A config the supervisor is allowed to fail over to: the one the user
+/// actually clicked, followed by anything they marked as a fallback.
+///
+/// `socks_port` is the *currently configured* `proxy_settings.socks_port` at
+/// connect time, not whatever port happened to be baked into `config_json`
+/// when the config was added - `spawn_v2ray` rewrites the config's inbound
+/// to this port so it never drifts out of sync with the OS proxy the
+/// caller just enabled.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub id: String,
+    pub config_json: String,
+    pub socks_port: u16,
+}
+
+/// Handle to the background watchdog task; cancel it to stop supervising
+/// (and kill the v2ray child) cleanly.
+pub struct Supervisor {
+    cancel: Arc<Notify>,
+    handle: JoinHandle<()>,
+}
+
+impl Supervisor {
+    /// `on_state` is invoked on every transition; the caller wires it up to
+    /// persist `ConnectionState` into `AppState` and emit it to the frontend.
+    pub fn spawn(
+        candidates: Vec<Candidate>,
+        config_dir: PathBuf,
+        on_state: impl Fn(ConnectionState) + Send + Sync + 'static,
+    ) -> Self {
+        let cancel = Arc::new(Notify::new());
+        let task_cancel = cancel.clone();
+        let handle = tokio::spawn(async move {
+            watch(candidates, config_dir, task_cancel, on_state).await;
+        });
+        Self { cancel, handle }
+    }
+
+    /// Stops the watchdog and waits for its v2ray child to be torn down.
+    pub async fn cancel(self) {
+        self.cancel.notify_one();
+        let _ = self.handle.await;
+    }
+}
+
+async fn watch(
+    candidates: Vec<Candidate>,
+    config_dir: PathBuf,
+    cancel: Arc<Notify>,
+    on_state: impl Fn(ConnectionState) + Send + Sync + 'static,
+) {
+    if candidates.is_empty() {
+        on_state(ConnectionState::Failed {
+            config_id: String::new(),
+            reason: "no candidate configs to connect to".into(),
+        });
+        return;
+    }
+
+    let mut index = 0usize;
+    let mut backoff = INITIAL_BACKOFF;
+    let mut consecutive_failures = 0u32;
+
+    loop {
+        let candidate = candidates[index].clone();
+        on_state(ConnectionState::Connecting { config_id: candidate.id.clone() });
+
+        match spawn_v2ray(&candidate, &config_dir) {
+            Ok(mut child) => {
+                on_state(ConnectionState::Connected { config_id: candidate.id.clone() });
+
+                let outcome = tokio::select! {
+                    _ = cancel.notified() => None,
+                    was_healthy = wait_for_trouble(&mut child, Some(candidate.socks_port)) => Some(was_healthy),
+                };
+                let _ = child.kill();
+                let Some(was_healthy) = outcome else {
+                    return;
+                };
+                // Only a tunnel that passed at least one health probe resets the
+                // backoff and failure count; one that dies or stalls straight
+                // away counts towards failover like a failed spawn.
+                if was_healthy {
+                    backoff = INITIAL_BACKOFF;
+                    consecutive_failures = 0;
+                }
+            }
+            Err(reason) => {
+                on_state(ConnectionState::Failed { config_id: candidate.id.clone(), reason });
+            }
+        }
+
+        consecutive_failures += 1;
+        if consecutive_failures >= FAILURES_BEFORE_ROTATE && candidates.len() > 1 {
+            index = (index + 1) % candidates.len();
+            consecutive_failures = 0;
+        }
+
+        on_state(ConnectionState::Reconnecting { config_id: candidates[index].id.clone() });
+
+        if !sleep_with_jitter_or_cancel(&cancel, &mut backoff).await {
+            return;
+        }
+    }
+}
+
+/// Rewrites `config_json`'s SOCKS inbound to `socks_port`, the same way
+/// `probe::with_scratch_port` patches a port in for one-off probes - so
+/// whatever port was baked in when the config was added never matters, only
+/// the currently configured one does.
+fn with_socks_port(config_json: &str, socks_port: u16) -> Result<String, String> {
+    let mut value: serde_json::Value = serde_json::from_str(config_json).map_err(|e| e.to_string())?;
+    if let Some(inbound) = value.get_mut("inbounds").and_then(|v| v.get_mut(0)) {
+        inbound["port"] = serde_json::json!(socks_port);
+    }
+    Ok(value.to_string())
+}
+
+fn spawn_v2ray(candidate: &Candidate, config_dir: &Path) -> Result<Child, String> {
+    let config_json = with_socks_port(&candidate.config_json, candidate.socks_port)?;
+    let config_file = config_dir.join("current_config.json");
+    std::fs::write(&config_file, &config_json).map_err(|e| e.to_string())?;
+
+    let v2ray_cmd = if cfg!(target_os = "windows") { "v2ray.exe" } else { "v2ray" };
+    Command::new(v2ray_cmd)
+        .arg("-config")
+        .arg(&config_file)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("failed to start v2ray: {e}"))
+}
+
+/// Sends a single request through `socks_port` and reports whether it
+/// succeeded; used to detect a stalled/unreachable tunnel that v2ray itself
+/// doesn't consider fatal enough to exit over.
+async fn probe_health(socks_port: u16) -> bool {
+    let Ok(proxy) = reqwest::Proxy::all(format!("socks5://127.0.0.1:{socks_port}")) else {
+        return false;
+    };
+    let Ok(client) = reqwest::Client::builder().proxy(proxy).timeout(HEALTH_PROBE_TIMEOUT).build() else {
+        return false;
+    };
+    matches!(client.get(HEALTH_PROBE_URL).send().await, Ok(response) if response.status().is_success())
+}
+
+/// Polls the child at `HEALTH_POLL_INTERVAL` until it exits on its own, or
+/// until a through-tunnel probe fails - v2ray commonly keeps running even
+/// when the upstream is unreachable or the tunnel has stalled. Returns
+/// whether at least one health probe passed before that.
+async fn wait_for_trouble(child: &mut Child, socks_port: Option<u16>) -> bool {
+    let mut was_healthy = false;
+    loop {
+        tokio::time::sleep(HEALTH_POLL_INTERVAL).await;
+        match child.try_wait() {
+            Ok(Some(_status)) => return was_healthy,
+            Ok(None) => {}
+            Err(_) => return was_healthy,
+        }
+        if let Some(port) = socks_port {
+            if !probe_health(port).await {
+                return was_healthy;
+            }
+            was_healthy = true;
+        }
+    }
+}
+
+/// Sleeps for `backoff` plus a little jitter, then doubles `backoff` (capped
+/// at `MAX_BACKOFF`) for next time. Returns `false` if cancelled mid-sleep.
+async fn sleep_with_jitter_or_cancel(cancel: &Notify, backoff: &mut Duration) -> bool {
+    let jitter = Duration::from_millis(rand::thread_rng().gen_range(0..100));
+    tokio::select! {
+        _ = cancel.notified() => false,
+        _ = tokio::time::sleep(*backoff + jitter) => {
+            *backoff = (*backoff * 2).min(MAX_BACKOFF);
+            true
+        }
+    }
+}